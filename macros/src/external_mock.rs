@@ -0,0 +1,143 @@
+//! Implements `mock_external!`, which generates mockable wrappers for fns you don't own (`std`,
+//! third-party crates) so tests can stub out I/O, clocks and the environment without touching
+//! the call site.
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    parse::{Parse, ParseStream},
+    Attribute, FnArg, Pat, Path, Signature, Token, Visibility,
+};
+
+#[cfg(feature = "mocking")]
+use crate::header_builder::build_header;
+#[cfg(feature = "mocking")]
+use crate::lifetime_normalizer::normalize_lifetimes;
+use crate::lifetime_normalizer::validate_arg_patterns;
+
+/// One `fn foo(...) -> Ret as real::path::to::foo;` declaration inside `mock_external!`.
+struct ExternalFnDecl {
+    attrs: Vec<Attribute>,
+    vis: Visibility,
+    sig: Signature,
+    real_path: Path,
+}
+
+impl Parse for ExternalFnDecl {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis: Visibility = input.parse()?;
+        let sig: Signature = input.parse()?;
+        if let Some(receiver) = sig.receiver() {
+            return Err(syn::Error::new_spanned(
+                receiver,
+                "mock_external! fns can't take a `self` receiver",
+            ));
+        }
+        input.parse::<Token![as]>()?;
+        let real_path: Path = input.parse()?;
+        input.parse::<Token![;]>()?;
+        Ok(ExternalFnDecl {
+            attrs,
+            vis,
+            sig,
+            real_path,
+        })
+    }
+}
+
+struct ExternalMockInput {
+    decls: Vec<ExternalFnDecl>,
+}
+
+impl Parse for ExternalMockInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut decls = Vec::new();
+        while !input.is_empty() {
+            decls.push(input.parse()?);
+        }
+        Ok(ExternalMockInput { decls })
+    }
+}
+
+/// Expands `mock_external! { fn foo(...) -> Ret as real::path::foo; ... }` into one wrapper fn
+/// per declaration.
+///
+/// Each wrapper has the declared name, visibility and signature, and forwards to `real_path`.
+/// With this crate's `mocking` feature on, the wrapper is injected with the same mock-check
+/// prologue [`crate::item_injector`] gives a `#[mockable]` fn, using the wrapper fn item itself
+/// as the mock key exactly like any other mockable fn; tests stub it the same way. With
+/// `mocking` off it's a plain, zero-overhead forwarding call.
+pub fn expand(input: TokenStream) -> TokenStream {
+    let parsed: ExternalMockInput = match syn::parse2(input) {
+        Ok(parsed) => parsed,
+        Err(err) => return err.to_compile_error(),
+    };
+    parsed.decls.into_iter().map(build_wrapper).collect()
+}
+
+fn arg_idents(sig: &Signature) -> Vec<syn::Ident> {
+    sig.inputs
+        .iter()
+        .map(|arg| match arg {
+            FnArg::Typed(pat_type) => match &*pat_type.pat {
+                Pat::Ident(pat_ident) => pat_ident.ident.clone(),
+                _ => unreachable!("non-ident argument patterns are rejected before this point"),
+            },
+            FnArg::Receiver(_) => unreachable!("mock_external! fns never take a receiver"),
+        })
+        .collect()
+}
+
+#[cfg(not(feature = "mocking"))]
+fn build_wrapper(decl: ExternalFnDecl) -> TokenStream {
+    let ExternalFnDecl {
+        attrs,
+        vis,
+        sig,
+        real_path,
+    } = decl;
+    if let Err(err) = validate_arg_patterns(&sig) {
+        let error = err.to_compile_error();
+        return quote! {
+            #(#attrs)*
+            #vis #sig {
+                #error
+            }
+        };
+    }
+    let arg_idents = arg_idents(&sig);
+    quote! {
+        #(#attrs)*
+        #vis #sig {
+            #real_path(#(#arg_idents,)*)
+        }
+    }
+}
+
+#[cfg(feature = "mocking")]
+fn build_wrapper(decl: ExternalFnDecl) -> TokenStream {
+    let ExternalFnDecl {
+        attrs,
+        vis,
+        mut sig,
+        real_path,
+    } = decl;
+    if let Err(err) = normalize_lifetimes(&mut sig) {
+        let error = err.to_compile_error();
+        return quote! {
+            #(#attrs)*
+            #vis #sig {
+                #error
+            }
+        };
+    }
+    let arg_idents = arg_idents(&sig);
+    let header = build_header(&sig);
+    quote! {
+        #(#attrs)*
+        #vis #sig {
+            #header
+            #real_path(#(#arg_idents,)*)
+        }
+    }
+}
@@ -0,0 +1,44 @@
+//! Builds the mock-check prologue injected at the top of a mockable function's body.
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{FnArg, Ident, Pat, Signature};
+
+/// Generates the statements that check the mock store before running the original body.
+///
+/// The prologue calls `mocktopus::mocking::Mockable::call_mock`, using the function item
+/// itself (`&#fn_ident`) as the mock key and forwarding every non-receiver argument as a tuple. When the
+/// mock store holds a stored behaviour for this function it is returned immediately; otherwise
+/// the original arguments are handed back unchanged so the real body can run.
+pub fn build_header(sig: &Signature) -> TokenStream {
+    let fn_ident = &sig.ident;
+    let arg_idents = get_arg_idents(sig);
+    quote! {
+        let (#(#arg_idents,)*) = match ::mocktopus::mocking::Mockable::call_mock(&#fn_ident, (#(#arg_idents,)*)) {
+            ::mocktopus::mocking::MockResult::Continue(__mocktopus_input) => __mocktopus_input,
+            ::mocktopus::mocking::MockResult::Return(__mocktopus_result) => return __mocktopus_result,
+        };
+    }
+}
+
+/// Collects the binding names of `sig`'s non-receiver arguments, in call order.
+///
+/// `self` is deliberately left out: it can't be bound as a tuple element or match pattern
+/// (`self` may not be bound to variables, E0424), and the method body already has it in scope
+/// unchanged, so there's nothing to round-trip for it.
+///
+/// Every remaining argument must be a plain identifier pattern (`x`, not `_` or `(a, b)`) so it
+/// can be round-tripped through the input tuple; this is enforced by
+/// [`crate::lifetime_normalizer::normalize_lifetimes`], which every caller runs before the
+/// header is built.
+fn get_arg_idents(sig: &Signature) -> Vec<Ident> {
+    sig.inputs
+        .iter()
+        .filter_map(|arg| match arg {
+            FnArg::Receiver(_) => None,
+            FnArg::Typed(pat_type) => match &*pat_type.pat {
+                Pat::Ident(pat_ident) => Some(pat_ident.ident.clone()),
+                _ => unreachable!("non-ident argument patterns are rejected before this point"),
+            },
+        })
+        .collect()
+}
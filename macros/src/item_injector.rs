@@ -0,0 +1,266 @@
+//! Walks a `syn::Item` and rewrites every mockable fn inside it to check the mock store first.
+use proc_macro2::TokenStream;
+use quote::ToTokens;
+use syn::{
+    parse::Parser, spanned::Spanned, visit::Visit, Attribute, Block, FnArg, ForeignItem,
+    ImplItem, Item, ItemForeignMod, ItemImpl, ItemMod, ItemTrait, Signature, TraitItem,
+};
+
+use crate::header_builder::build_header;
+use crate::lifetime_normalizer::normalize_lifetimes;
+
+/// Recursively rewrites `item` in place so every mockable fn it contains gets a mock-check
+/// prologue inserted at the top of its body, and returns the tokens to emit in its place.
+///
+/// - standalone fns are injected directly
+/// - impl and trait blocks have every one of their fns injected
+/// - modules have every one of their items injected (recursively)
+/// - `extern` blocks have a safe mockable shim generated for each foreign fn declaration
+/// - anything else (structs, consts, statics, ...) is left untouched
+///
+/// If `item` is one of the documented-invalid annotation contexts, `item` is returned unchanged
+/// with a `compile_error!` appended rather than being injected, so misuse fails loudly at the
+/// call site instead of silently breaking mocking.
+pub fn inject_item(item: &mut Item) -> TokenStream {
+    if let Some(err) = invalid_context_error(item) {
+        let mut tokens = item.to_token_stream();
+        tokens.extend(err.to_compile_error());
+        *item = Item::Verbatim(tokens.clone());
+        return tokens;
+    }
+    match item {
+        Item::Fn(item_fn) => inject_fn(&mut item_fn.sig, &mut item_fn.block, &item_fn.attrs),
+        Item::Impl(item_impl) => inject_impl(item_impl),
+        Item::Trait(item_trait) => inject_trait(item_trait),
+        Item::Mod(item_mod) => inject_mod(item_mod),
+        Item::ForeignMod(_) => inject_foreign_mod_item(item),
+        _ => {}
+    }
+    item.to_token_stream()
+}
+
+/// Pinpoints the annotation contexts the doc comment on [`crate::mockable`] lists as invalid:
+/// `#[mockable]` placed directly on a single method inside an `impl`/`trait` block instead of on
+/// the enclosing block. Such a method still parses as an ordinary `syn::Item::Fn`, indistinguishable
+/// from a real standalone fn in the general case, but two syntactic tells catch the common ones:
+/// only a method can take a `self` receiver, and only a method or associated fn can refer to
+/// `Self` in its own signature without that type being otherwise in scope.
+///
+/// This doesn't catch every case: a receiver-less associated fn that never mentions `Self` in
+/// its signature (e.g. `fn helper(x: i32) { ... }` inside an `impl`) is still indistinguishable
+/// from a real standalone fn and will be silently misinjected. There's no syntactic signal left
+/// to catch that one short of seeing the enclosing item, which `#[mockable]` never does.
+fn invalid_context_error(item: &Item) -> Option<syn::Error> {
+    let Item::Fn(item_fn) = item else {
+        return None;
+    };
+    let has_receiver = item_fn
+        .sig
+        .inputs
+        .iter()
+        .any(|arg| matches!(arg, FnArg::Receiver(_)));
+    if !has_receiver && !signature_references_self_type(&item_fn.sig) {
+        return None;
+    }
+    Some(syn::Error::new(
+        item_fn.sig.ident.span(),
+        format!(
+            "#[mockable] can't be placed directly on method `{}`; annotate the enclosing impl/trait block instead",
+            item_fn.sig.ident,
+        ),
+    ))
+}
+
+/// Whether `sig` itself (its generics, arguments and return type — never the fn body) mentions
+/// the `Self` type, which is only meaningful inside an `impl`/`trait` block — a reliable tell
+/// that a fn parsed as a lone `syn::Item::Fn` actually belongs to one.
+///
+/// Deliberately doesn't look at the body: a standalone fn's body can legitimately contain a
+/// nested `impl`/`trait` of its own that mentions `Self` in *its* scope, which isn't a signal
+/// about the outer fn at all.
+fn signature_references_self_type(sig: &Signature) -> bool {
+    struct SelfTypeFinder {
+        found: bool,
+    }
+    impl<'ast> Visit<'ast> for SelfTypeFinder {
+        fn visit_ident(&mut self, ident: &'ast syn::Ident) {
+            if ident == "Self" {
+                self.found = true;
+            }
+        }
+    }
+    let mut finder = SelfTypeFinder { found: false };
+    finder.visit_signature(sig);
+    finder.found
+}
+
+/// A foreign fn's wrapper lives next to the `extern` block, not inside it (`extern` blocks may
+/// only contain foreign item *declarations*, never a fn with a body). Since [`inject_item`] can
+/// only mutate a single `Item` in place, the block and its generated wrappers are stitched
+/// together into one `Item::Verbatim` token stream standing in for both.
+fn inject_foreign_mod_item(item: &mut Item) {
+    let placeholder = Item::Verbatim(TokenStream::new());
+    let Item::ForeignMod(mut item_foreign_mod) = std::mem::replace(item, placeholder) else {
+        unreachable!("caller only invokes this for Item::ForeignMod");
+    };
+    *item = inject_foreign_mod(&mut item_foreign_mod);
+}
+
+fn is_not_mockable(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| attr.path().is_ident("not_mockable"))
+}
+
+fn has_link_name_attr(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| attr.path().is_ident("link_name"))
+}
+
+fn inject_fn(sig: &mut Signature, block: &mut Block, attrs: &[Attribute]) {
+    if is_not_mockable(attrs) || sig.constness.is_some() || sig.unsafety.is_some() {
+        return;
+    }
+    if let Err(err) = normalize_lifetimes(sig) {
+        block.stmts.insert(0, compile_error_stmt(err));
+        return;
+    }
+    let header = build_header(sig);
+    let header_stmts = syn::Block::parse_within
+        .parse2(header)
+        .expect("generated mock header must parse as statements");
+    block.stmts.splice(0..0, header_stmts);
+}
+
+/// Turns a [`syn::Error`] into a statement that fails the build with that error's message,
+/// pointed at its original span, without touching the rest of the fn's body.
+fn compile_error_stmt(err: syn::Error) -> syn::Stmt {
+    syn::parse2(err.to_compile_error()).expect("compile_error! tokens parse as a statement")
+}
+
+fn inject_impl(item_impl: &mut ItemImpl) {
+    if is_not_mockable(&item_impl.attrs) {
+        return;
+    }
+    for impl_item in &mut item_impl.items {
+        if let ImplItem::Fn(method) = impl_item {
+            inject_fn(&mut method.sig, &mut method.block, &method.attrs);
+        }
+    }
+}
+
+fn inject_trait(item_trait: &mut ItemTrait) {
+    if is_not_mockable(&item_trait.attrs) {
+        return;
+    }
+    for trait_item in &mut item_trait.items {
+        if let TraitItem::Fn(method) = trait_item {
+            if let Some(block) = &mut method.default {
+                inject_fn(&mut method.sig, block, &method.attrs);
+            }
+        }
+    }
+}
+
+fn inject_mod(item_mod: &mut ItemMod) {
+    if is_not_mockable(&item_mod.attrs) {
+        return;
+    }
+    if let Some((_, items)) = &mut item_mod.content {
+        for item in items {
+            if !item_is_not_mockable(item) {
+                inject_item(item);
+            }
+        }
+    }
+}
+
+fn item_is_not_mockable(item: &Item) -> bool {
+    match item {
+        Item::Fn(item_fn) => is_not_mockable(&item_fn.attrs),
+        Item::Impl(item_impl) => is_not_mockable(&item_impl.attrs),
+        Item::Trait(item_trait) => is_not_mockable(&item_trait.attrs),
+        Item::Mod(item_mod) => is_not_mockable(&item_mod.attrs),
+        Item::ForeignMod(item_foreign_mod) => is_not_mockable(&item_foreign_mod.attrs),
+        _ => false,
+    }
+}
+
+/// Replaces each `fn` declaration in an `extern "ABI" { ... }` block with a safe wrapper fn of
+/// the same name, signature and visibility, and renames the original declaration so the wrapper
+/// can still reach the real foreign symbol.
+///
+/// An `extern` block is not a module: it introduces no path segment of its own, so unlike
+/// [`inject_mod`] this does not add any extra visibility or path indirection for the generated
+/// wrappers, and argument/return types written relative to the block stay resolved exactly as
+/// they were in the surrounding scope (one level up from the block's own items).
+fn inject_foreign_mod(item_foreign_mod: &mut ItemForeignMod) -> Item {
+    if is_not_mockable(&item_foreign_mod.attrs) {
+        return Item::ForeignMod(item_foreign_mod.clone());
+    }
+    let mut wrappers = TokenStream::new();
+    for foreign_item in &mut item_foreign_mod.items {
+        if let ForeignItem::Fn(foreign_fn) = foreign_item {
+            if is_not_mockable(&foreign_fn.attrs) {
+                continue;
+            }
+            wrappers.extend(build_foreign_fn_wrapper(foreign_fn));
+        }
+    }
+    let mut combined = quote::quote! { #item_foreign_mod };
+    combined.extend(wrappers);
+    Item::Verbatim(combined)
+}
+
+/// Renames `extern "ABI" { fn foo(...); }`'s declaration to `__mocktopus_real_foo` (pinning its
+/// linked symbol name to the original `foo` via `#[link_name]` so the rename doesn't also
+/// relink it) and returns a same-visibility safe `fn foo(...)` that checks the mock store before
+/// calling it.
+fn build_foreign_fn_wrapper(foreign_fn: &mut syn::ForeignItemFn) -> TokenStream {
+    let wrapper_ident = foreign_fn.sig.ident.clone();
+    let real_ident = syn::Ident::new(
+        &format!("__mocktopus_real_{}", wrapper_ident),
+        wrapper_ident.span(),
+    );
+    // Renaming the declaration also renames the symbol the linker looks for, so the original
+    // name must be pinned down explicitly first (unless the user already pinned one themselves).
+    if !has_link_name_attr(&foreign_fn.attrs) {
+        let original_name = wrapper_ident.to_string();
+        foreign_fn
+            .attrs
+            .push(syn::parse_quote!(#[link_name = #original_name]));
+    }
+    foreign_fn.sig.ident = real_ident.clone();
+
+    let mut wrapper_sig = foreign_fn.sig.clone();
+    wrapper_sig.ident = wrapper_ident;
+    wrapper_sig.unsafety = None;
+
+    if let Err(err) = normalize_lifetimes(&mut wrapper_sig) {
+        let error = err.to_compile_error();
+        let vis = &foreign_fn.vis;
+        return quote::quote! {
+            #vis #wrapper_sig {
+                #error
+            }
+        };
+    }
+
+    let arg_idents: Vec<_> = wrapper_sig
+        .inputs
+        .iter()
+        .map(|arg| match arg {
+            syn::FnArg::Typed(pat_type) => match &*pat_type.pat {
+                syn::Pat::Ident(pat_ident) => pat_ident.ident.clone(),
+                _ => unreachable!("non-ident argument patterns are rejected before this point"),
+            },
+            syn::FnArg::Receiver(_) => unreachable!("extern fns never take a receiver"),
+        })
+        .collect();
+    let header = build_header(&wrapper_sig);
+    let vis = &foreign_fn.vis;
+
+    quote::quote! {
+        #vis #wrapper_sig {
+            #header
+            unsafe { #real_ident(#(#arg_idents,)*) }
+        }
+    }
+}
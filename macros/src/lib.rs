@@ -8,12 +8,14 @@
 extern crate proc_macro;
 
 mod display_delegate;
+mod external_mock;
+#[cfg(feature = "mocking")]
 mod header_builder;
+#[cfg(feature = "mocking")]
 mod item_injector;
+mod lifetime_normalizer;
 
-use proc_macro::{Span, TokenStream};
-use quote::ToTokens;
-use log::{info, warn};
+use proc_macro::TokenStream;
 
 /// Procedural macro, makes items and their sub-items mockable
 ///
@@ -34,6 +36,14 @@ use log::{info, warn};
 /// #[mockable]
 /// fn mockable() { ... }
 /// ```
+/// - `extern` blocks (makes all foreign fns inside mockable)
+///
+/// ```
+/// #[mockable]
+/// extern "C" {
+///     fn mockable();
+/// }
+/// ```
 /// - struct impl blocks (makes all functions inside mockable)
 ///
 /// ```
@@ -96,21 +106,43 @@ use log::{info, warn};
 /// - unsafe functions (they are impossible to mock)
 /// - any macro generated items (they are impossible to mock)
 /// - any other items
+///
+/// With this crate's own `mocking` feature disabled (the default for release profiles of
+/// downstream crates), this expands to a no-op, identically to [`not_mockable`]. No trampolines
+/// are injected, so annotated items cost nothing beyond the attribute lookup itself.
+///
+/// Proc macro crates can't see a downstream crate's enabled features directly, so this feature
+/// lives on `mocktopus-macros` itself rather than being probed at a distance. The `mocktopus`
+/// support crate forwards its own same-named `mocking` feature straight through to it:
+///
+/// ```toml
+/// # mocktopus/Cargo.toml
+/// [features]
+/// mocking = ["mocktopus-macros/mocking"]
+/// ```
+///
+/// so enabling or disabling `mocktopus/mocking` is all a downstream `Cargo.toml` has to do.
+#[cfg(not(feature = "mocking"))]
+#[proc_macro_attribute]
+pub fn mockable(_: TokenStream, token_stream: TokenStream) -> TokenStream {
+    token_stream
+}
+
+#[cfg(feature = "mocking")]
 #[proc_macro_attribute]
 pub fn mockable(_: TokenStream, token_stream: TokenStream) -> TokenStream {
     let mut item: syn::Item = match syn::parse(token_stream.clone()) {
         Ok(item) => item,
         Err(err) => {
-            // Span::call_site()
-            //     .warning("Failed to make code mockable")
-            //     .error(format!("Failed to parse: {}", err))
-            //     .emit();
-            warn!("Failed to parse token stream: {}", err);
-            return token_stream;
+            // Keep the original tokens so the rest of the file still compiles, and point the
+            // user at the parse failure with a `compile_error!` instead of a silent `warn!` that
+            // only shows up in a log nobody's watching.
+            let mut tokens: proc_macro2::TokenStream = token_stream.into();
+            tokens.extend(err.to_compile_error());
+            return tokens.into();
         }
     };
-    item_injector::inject_item(&mut item);
-    item.into_token_stream().into()
+    item_injector::inject_item(&mut item).into()
 }
 
 /// Procedural macro, guards items from being made mockable by enclosing item.
@@ -204,3 +236,22 @@ pub fn mockable(_: TokenStream, token_stream: TokenStream) -> TokenStream {
 pub fn not_mockable(_: TokenStream, token_stream: TokenStream) -> TokenStream {
     token_stream
 }
+
+/// Generates mockable wrappers for fns you don't own, such as `std` or a third-party crate,
+/// that can't be annotated with [`mockable`] directly.
+///
+/// Takes a list of `fn` declarations, each pointing at the real fn it wraps with `as`:
+///
+/// ```
+/// mock_external! {
+///     pub fn read_to_string(path: &Path) -> io::Result<String> as std::fs::read_to_string;
+/// }
+/// ```
+///
+/// expands to a real `read_to_string` fn with the declared signature that forwards to
+/// `std::fs::read_to_string`. Production code calls the wrapper instead of the original, and
+/// tests mock it exactly like any `#[mockable]` fn, using the wrapper itself as the mock key.
+#[proc_macro]
+pub fn mock_external(token_stream: TokenStream) -> TokenStream {
+    external_mock::expand(token_stream.into()).into()
+}
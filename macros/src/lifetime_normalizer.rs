@@ -0,0 +1,154 @@
+//! Makes every lifetime in a mockable fn's signature explicit.
+//!
+//! The prologue from [`crate::header_builder`] hands the fn item itself to the mock store as its
+//! key, so the store works with the argument and return types of the fn's signature. Elided
+//! lifetimes (`fn get(&self) -> &Foo`) are made explicit in that signature before the header is
+//! built, so every reference lifetime there has a name.
+use proc_macro2::Span;
+use syn::{
+    visit_mut::{self, VisitMut},
+    FnArg, GenericParam, Lifetime, LifetimeParam, Pat, ReturnType, Signature, TypeReference,
+};
+
+/// Rewrites `sig` in place: mints a fresh named lifetime (`'__mocktopus_<name>`) for every
+/// elided reference lifetime among its receiver and arguments, adds it to the signature's
+/// generics, and binds any elided lifetime in the return type to the receiver's lifetime if
+/// there is a reference receiver, otherwise to the lifetime minted for the arguments if exactly
+/// one was minted. Lifetimes written out explicitly in the arguments are not counted for this;
+/// when no binding is picked, the return type's elided lifetimes are left as they are.
+///
+/// Returns an error if an argument isn't a plain named binding, since `_`, `ref` bindings and
+/// sub-patterns can't be round-tripped through the mock-check's input tuple.
+pub fn normalize_lifetimes(sig: &mut Signature) -> syn::Result<()> {
+    let mut minted = Vec::new();
+    let mut receiver_lifetime = None;
+
+    if let Some(FnArg::Receiver(receiver)) = sig.inputs.first_mut() {
+        if let Some((_, lifetime)) = &mut receiver.reference {
+            match lifetime {
+                Some(existing) => receiver_lifetime = Some(existing.clone()),
+                None => {
+                    let fresh = fresh_lifetime("self");
+                    *lifetime = Some(fresh.clone());
+                    minted.push(fresh.clone());
+                    receiver_lifetime = Some(fresh);
+                }
+            }
+        }
+    }
+
+    let has_receiver = matches!(sig.inputs.first(), Some(FnArg::Receiver(_)));
+    let mut arg_lifetimes = Vec::new();
+    for arg in sig.inputs.iter_mut().skip(has_receiver as usize) {
+        let FnArg::Typed(pat_type) = arg else {
+            continue;
+        };
+        let name = require_plain_ident(&pat_type.pat)?;
+        let mut filler = ElisionFiller::new(name);
+        filler.visit_type_mut(&mut pat_type.ty);
+        minted.extend(filler.minted.iter().cloned());
+        arg_lifetimes.extend(filler.minted);
+    }
+
+    // Lifetime params must precede type/const params in a generic param list, so they're
+    // inserted at the front rather than pushed: a fn like `fn first<T>(xs: &[T]) -> &T` already
+    // has a type param, and appending would emit the invalid `fn first<T, '__mocktopus_xs>`.
+    for lifetime in minted.iter().rev() {
+        sig.generics
+            .params
+            .insert(0, GenericParam::Lifetime(LifetimeParam::new(lifetime.clone())));
+    }
+
+    let return_lifetime = receiver_lifetime.or_else(|| {
+        if arg_lifetimes.len() == 1 {
+            Some(arg_lifetimes.remove(0))
+        } else {
+            None
+        }
+    });
+    if let (Some(lifetime), ReturnType::Type(_, ty)) = (return_lifetime, &mut sig.output) {
+        ElisionBinder { lifetime }.visit_type_mut(ty);
+    }
+    Ok(())
+}
+
+/// Checks that every non-receiver argument in `sig` is a plain named binding, without rewriting
+/// any lifetimes. For call sites (like `mock_external!`'s zero-overhead, mocking-off expansion)
+/// that still forward arguments by name but don't need lifetime explicitization.
+pub fn validate_arg_patterns(sig: &Signature) -> syn::Result<()> {
+    for arg in &sig.inputs {
+        if let FnArg::Typed(pat_type) = arg {
+            require_plain_ident(&pat_type.pat)?;
+        }
+    }
+    Ok(())
+}
+
+fn require_plain_ident(pat: &Pat) -> syn::Result<String> {
+    match pat {
+        Pat::Ident(pat_ident) if pat_ident.by_ref.is_none() && pat_ident.subpat.is_none() => {
+            Ok(pat_ident.ident.to_string())
+        }
+        other => Err(syn::Error::new_spanned(
+            other,
+            "mockable fn arguments must be plain named bindings (not `_`, `ref`, or a \
+             sub-pattern) so they can be round-tripped through the mock store",
+        )),
+    }
+}
+
+fn fresh_lifetime(name: &str) -> Lifetime {
+    Lifetime::new(&format!("'__mocktopus_{name}"), Span::call_site())
+}
+
+/// Mints a distinct fresh lifetime for every elided reference found in one argument's type.
+struct ElisionFiller {
+    base_name: String,
+    seen: usize,
+    minted: Vec<Lifetime>,
+}
+
+impl ElisionFiller {
+    fn new(base_name: String) -> Self {
+        ElisionFiller {
+            base_name,
+            seen: 0,
+            minted: Vec::new(),
+        }
+    }
+
+    fn next_lifetime(&mut self) -> Lifetime {
+        let suffix = if self.seen == 0 {
+            String::new()
+        } else {
+            self.seen.to_string()
+        };
+        self.seen += 1;
+        fresh_lifetime(&format!("{}{}", self.base_name, suffix))
+    }
+}
+
+impl VisitMut for ElisionFiller {
+    fn visit_type_reference_mut(&mut self, node: &mut TypeReference) {
+        if node.lifetime.is_none() {
+            let fresh = self.next_lifetime();
+            self.minted.push(fresh.clone());
+            node.lifetime = Some(fresh);
+        }
+        visit_mut::visit_type_reference_mut(self, node);
+    }
+}
+
+/// Binds every elided reference lifetime found in a type to one fixed, already-known lifetime.
+struct ElisionBinder {
+    lifetime: Lifetime,
+}
+
+impl VisitMut for ElisionBinder {
+    fn visit_type_reference_mut(&mut self, node: &mut TypeReference) {
+        if node.lifetime.is_none() {
+            node.lifetime = Some(self.lifetime.clone());
+        }
+        visit_mut::visit_type_reference_mut(self, node);
+    }
+}